@@ -16,8 +16,18 @@ pub enum Side {
     Ask,
 }
 
+/// Source of the reference price used by price-relative features
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
+pub enum ReferenceSource {
+    LastTrade,
+    #[default]
+    Mid,
+    External(f64),
+    BestOfBook,
+}
+
 // https://stackoverflow.com/a/32936064
-thread_local!(static CLIENT_ID: Cell<u64> = Cell::new(0));
+thread_local!(static CLIENT_ID: Cell<u64> = const { Cell::new(0) });
 
 #[derive(Debug)]
 pub struct Client {
@@ -40,7 +50,7 @@ impl fmt::Display for Client {
     }
 }
 
-thread_local!(static ORDER_ID: Cell<u64> = Cell::new(0));
+thread_local!(static ORDER_ID: Cell<u64> = const { Cell::new(0) });
 
 #[derive(Debug)]
 pub struct Order {
@@ -96,6 +106,10 @@ pub struct OrderBook {
     bids: Ladder,
     asks: Ladder,
     lookup: HashMap<u64, (Side, f64)>,
+    min_print_size: u64,
+    reference_source: ReferenceSource,
+    last_trade_price: Option<f64>,
+    spread_range: Option<(f64, f64)>,
 }
 
 impl OrderBook {
@@ -103,12 +117,22 @@ impl OrderBook {
         Self::default()
     }
 
+    /// Fills smaller than `size` are not printed on their own, but are
+    /// aggregated into the next print of the same match
+    pub fn set_min_print_size(&mut self, size: u64) {
+        self.min_print_size = size;
+    }
+
+    pub fn set_reference_source(&mut self, source: ReferenceSource) {
+        self.reference_source = source;
+    }
+
     pub fn insert(&mut self, order: Order) -> OrderBookResult {
         if let Err(e) = self.validate_order(&order) {
             return OrderBookResult::Error(e);
         }
 
-        if self.is_passive(&order) {
+        let result = if self.is_passive(&order) {
             OrderBookResult::OrderId(self.place_passive(order))
         } else {
             let mut order = Box::new(order);
@@ -121,7 +145,10 @@ impl OrderBook {
                     OrderBookResult::OrderIdTrades(order_id, trades.unwrap_or_default())
                 }
             }
-        }
+        };
+
+        self.update_spread_range();
+        result
     }
 
     pub fn cancel(&mut self, order_id: u64) -> OrderBookResult {
@@ -134,9 +161,10 @@ impl OrderBook {
                 ladder.remove(&OrderedFloat(price));
             }
 
-            return OrderBookResult::Canceled;
+            self.update_spread_range();
+            OrderBookResult::Canceled
         } else {
-            return OrderBookResult::Error("Order does not exist");
+            OrderBookResult::Error("Order does not exist")
         }
     }
 
@@ -168,6 +196,8 @@ impl OrderBook {
     fn match_order(&mut self, order: &mut Order) -> Option<Vec<Trade>> {
         let mut empty_levels: Vec<OrderedFloat<f64>> = Vec::new();
         let mut trades: Vec<Trade> = Vec::new();
+        let mut unprinted_size = 0;
+        let mut last_trade_price = None;
         let ladder = match order.side {
             Side::Bid => &mut self.asks,
             Side::Ask => &mut self.bids,
@@ -189,10 +219,15 @@ impl OrderBook {
                 }
 
                 let trade_size = min(level_order.size, order.size);
-                let trade = Trade::new(level_price, trade_size);
                 level_order.size -= trade_size;
                 order.size -= trade_size;
-                trades.push(trade);
+                unprinted_size += trade_size;
+                last_trade_price = Some(level_price);
+
+                if unprinted_size >= self.min_print_size {
+                    trades.push(Trade::new(level_price, unprinted_size));
+                    unprinted_size = 0;
+                }
             }
 
             level.retain(|order| order.size > 0);
@@ -206,12 +241,25 @@ impl OrderBook {
             ladder.remove(level_price);
         }
 
+        if last_trade_price.is_some() {
+            self.last_trade_price = last_trade_price;
+        }
+
         match trades.is_empty() {
             false => Some(trades),
             true => None,
         }
     }
 
+    fn update_spread_range(&mut self) {
+        if let Some(spread) = self.spread() {
+            self.spread_range = match self.spread_range {
+                Some((tightest, widest)) => Some((tightest.min(spread), widest.max(spread))),
+                None => Some((spread, spread)),
+            };
+        }
+    }
+
     fn get_size(&self, side: Side, price: f64) -> u64 {
         self.get_ladder(&side)
             .get(&OrderedFloat(price))
@@ -234,12 +282,12 @@ impl OrderBook {
 
     /// Best bid price
     pub fn best_bid(&self) -> Option<f64> {
-        self.bids.keys().rev().next().map(|bid| bid.into_inner())
+        self.bids.keys().next_back().map(|bid| bid.into_inner())
     }
 
     /// Volume of all orders at best bid price
     pub fn best_bid_size(&self) -> Option<u64> {
-        self.bids.values().rev().next().map(get_level_size)
+        self.bids.values().next_back().map(get_level_size)
     }
 
     pub fn best_ask(&self) -> Option<f64> {
@@ -250,6 +298,80 @@ impl OrderBook {
         self.asks.values().next().map(get_level_size)
     }
 
+    /// Difference between best ask and best bid prices
+    pub fn spread(&self) -> Option<f64> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some(bid), Some(ask)) => Some(ask - bid),
+            _ => None,
+        }
+    }
+
+    /// Tightest and widest spread observed since the book was created
+    pub fn spread_range(&self) -> Option<(f64, f64)> {
+        self.spread_range
+    }
+
+    /// Price of the most recent fill
+    pub fn last_trade_price(&self) -> Option<f64> {
+        self.last_trade_price
+    }
+
+    /// Reference price for an order on the `side` according to the
+    /// configured `ReferenceSource`
+    pub fn reference_price(&self, side: Side) -> Option<f64> {
+        match self.reference_source {
+            ReferenceSource::LastTrade => self.last_trade_price,
+            ReferenceSource::Mid => match (self.best_bid(), self.best_ask()) {
+                (Some(bid), Some(ask)) => Some((bid + ask) / 2.0),
+                _ => None,
+            },
+            ReferenceSource::External(price) => Some(price),
+            ReferenceSource::BestOfBook => match side {
+                Side::Bid => self.best_bid(),
+                Side::Ask => self.best_ask(),
+            },
+        }
+    }
+
+    /// Volume weighted average price of all orders resting on the `side`
+    pub fn book_vwap(&self, side: Side) -> Option<f64> {
+        let (notional, volume) =
+            self.get_ladder(&side)
+                .iter()
+                .fold((0.0, 0), |(notional, volume), (price, level)| {
+                    let size = get_level_size(level);
+                    (notional + price.into_inner() * size as f64, volume + size)
+                });
+
+        match volume {
+            0 => None,
+            _ => Some(notional / volume as f64),
+        }
+    }
+
+    /// Deepest price level a market order of `size` on the `side` would
+    /// reach while sweeping the opposite side of the book
+    pub fn worst_fill_price(&self, side: Side, size: u64) -> Option<f64> {
+        if size == 0 {
+            return None;
+        }
+
+        let mut remaining = size;
+
+        for (level_price, level) in match side {
+            Side::Bid => Either::Left(self.asks.iter()),
+            Side::Ask => Either::Right(self.bids.iter().rev()),
+        } {
+            remaining = remaining.saturating_sub(get_level_size(level));
+
+            if remaining == 0 {
+                return Some(level_price.into_inner());
+            }
+        }
+
+        None
+    }
+
     fn is_passive(&self, order: &Order) -> bool {
         let best_bid = self.best_bid();
         let best_ask = self.best_ask();
@@ -268,7 +390,7 @@ impl OrderBook {
 
 impl fmt::Display for OrderBook {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut msg: String = format!("Bid Qty   Price   Ask Qty\n");
+        let mut msg: String = "Bid Qty   Price   Ask Qty\n".to_string();
         msg = format!("{}--------+-------+--------\n", msg);
 
         for ask in self.asks.keys().rev().take(5) {
@@ -293,7 +415,7 @@ impl fmt::Display for OrderBook {
     }
 }
 
-thread_local!(static TRADE_ID: Cell<u64> = Cell::new(0));
+thread_local!(static TRADE_ID: Cell<u64> = const { Cell::new(0) });
 
 pub struct Trade {
     id: u64,
@@ -413,7 +535,7 @@ mod test {
 
     #[rstest]
     fn test_valid_order(#[by_ref] ob: &OrderBook, #[by_ref] order: &Order) {
-        assert!(ob.validate_order(&order).is_ok());
+        assert!(ob.validate_order(order).is_ok());
     }
 
     #[rstest]
@@ -441,8 +563,8 @@ mod test {
 
     #[rstest]
     fn test_best_bid(mut ob: OrderBook, client: Rc<Client>) {
-        let prices = vec![1.4, 1.5, 1.6, 1.3, 1.8, 1.4];
-        let sizes = vec![1, 2, 3, 4, 5, 6];
+        let prices = [1.4, 1.5, 1.6, 1.3, 1.8, 1.4];
+        let sizes = [1, 2, 3, 4, 5, 6];
 
         for (price, size) in prices.iter().zip(sizes.iter()) {
             let order = Order::new(Side::Bid, *price, *size, &client);
@@ -455,8 +577,8 @@ mod test {
 
     #[rstest]
     fn test_best_ask(mut ob: OrderBook, client: Rc<Client>) {
-        let prices = vec![1.4, 1.5, 1.6, 1.3, 1.8, 1.4];
-        let sizes = vec![1, 2, 3, 4, 5, 6];
+        let prices = [1.4, 1.5, 1.6, 1.3, 1.8, 1.4];
+        let sizes = [1, 2, 3, 4, 5, 6];
 
         for (price, size) in prices.iter().zip(sizes.iter()) {
             let order = Order::new(Side::Ask, *price, *size, &client);
@@ -484,4 +606,118 @@ mod test {
 
         assert_eq!(ob.best_ask_size(), Some(1));
     }
+
+    #[rstest]
+    fn test_book_vwap(mut ob: OrderBook, client: Rc<Client>) {
+        assert_eq!(ob.book_vwap(Side::Ask), None);
+
+        let prices = [1.1, 1.2, 1.3, 1.1];
+        let sizes = [3, 2, 6, 2];
+
+        for (price, size) in prices.iter().zip(sizes.iter()) {
+            let order = Order::new(Side::Ask, *price, *size, &client);
+            ob.insert(order);
+        }
+
+        let expected = (1.1 * 5.0 + 1.2 * 2.0 + 1.3 * 6.0) / 13.0;
+        assert!((ob.book_vwap(Side::Ask).unwrap() - expected).abs() < EPSILON);
+        assert_eq!(ob.book_vwap(Side::Bid), None);
+    }
+
+    #[rstest]
+    fn test_worst_fill_price(mut ob: OrderBook, client: Rc<Client>) {
+        let prices = [1.1, 1.2, 1.3, 1.4];
+        let sizes = [3, 2, 6, 1];
+
+        for (price, size) in prices.iter().zip(sizes.iter()) {
+            let order = Order::new(Side::Ask, *price, *size, &client);
+            ob.insert(order);
+        }
+
+        assert_eq!(ob.worst_fill_price(Side::Bid, 3), Some(1.1));
+        assert_eq!(ob.worst_fill_price(Side::Bid, 8), Some(1.3));
+        assert_eq!(ob.worst_fill_price(Side::Bid, 13), None);
+        assert_eq!(ob.worst_fill_price(Side::Ask, 1), None);
+    }
+
+    #[rstest]
+    fn test_min_print_size(mut ob: OrderBook, client: Rc<Client>) {
+        ob.set_min_print_size(3);
+
+        for size in [1, 1, 2, 1] {
+            ob.insert(Order::new(Side::Ask, 1.1, size, &client));
+        }
+        ob.insert(Order::new(Side::Ask, 1.2, 4, &client));
+
+        let order = Order::new(Side::Bid, 1.2, 7, &client);
+
+        if let OrderBookResult::Trades(trades) = ob.insert(order) {
+            assert_eq!(trades.len(), 2);
+            assert_eq!((trades[0].price, trades[0].size), (1.1, 4));
+            assert_eq!((trades[1].price, trades[1].size), (1.2, 3));
+        } else {
+            unreachable!();
+        }
+
+        assert_eq!(ob.best_ask(), Some(1.2));
+        assert_eq!(ob.best_ask_size(), Some(2));
+
+        // trailing fills below the threshold are not printed at all
+        let order = Order::new(Side::Bid, 1.2, 2, &client);
+
+        if let OrderBookResult::Trades(trades) = ob.insert(order) {
+            assert!(trades.is_empty());
+        } else {
+            unreachable!();
+        }
+
+        assert_eq!(ob.best_ask(), None);
+    }
+
+    #[rstest]
+    fn test_reference_price(mut ob: OrderBook, client: Rc<Client>) {
+        ob.insert(Order::new(Side::Bid, 1.0, 5, &client));
+        ob.insert(Order::new(Side::Ask, 1.2, 5, &client));
+        ob.insert(Order::new(Side::Ask, 1.6, 5, &client));
+        assert_eq!(ob.reference_price(Side::Bid), Some(1.1));
+
+        ob.set_reference_source(ReferenceSource::LastTrade);
+        assert_eq!(ob.reference_price(Side::Bid), None);
+
+        ob.insert(Order::new(Side::Bid, 1.2, 5, &client));
+        assert_eq!(ob.reference_price(Side::Bid), Some(1.2));
+
+        ob.set_reference_source(ReferenceSource::Mid);
+        assert_eq!(ob.reference_price(Side::Bid), Some(1.3));
+
+        ob.set_reference_source(ReferenceSource::BestOfBook);
+        assert_eq!(ob.reference_price(Side::Bid), Some(1.0));
+        assert_eq!(ob.reference_price(Side::Ask), Some(1.6));
+
+        ob.set_reference_source(ReferenceSource::External(1.05));
+        assert_eq!(ob.reference_price(Side::Ask), Some(1.05));
+    }
+
+    #[rstest]
+    fn test_spread_range(mut ob: OrderBook, client: Rc<Client>) {
+        ob.insert(Order::new(Side::Bid, 1.0, 5, &client));
+        assert_eq!(ob.spread_range(), None);
+
+        ob.insert(Order::new(Side::Ask, 1.5, 5, &client));
+        ob.insert(Order::new(Side::Ask, 1.25, 5, &client));
+        ob.insert(Order::new(Side::Bid, 1.125, 5, &client));
+
+        let order_id = match ob.insert(Order::new(Side::Ask, 1.25, 10, &client)) {
+            OrderBookResult::OrderId(id) => id,
+            _ => unreachable!(),
+        };
+        ob.insert(Order::new(Side::Bid, 1.25, 5, &client));
+        ob.cancel(order_id);
+        ob.insert(Order::new(Side::Ask, 2.0, 5, &client));
+        ob.insert(Order::new(Side::Bid, 1.5, 5, &client));
+        ob.insert(Order::new(Side::Bid, 1.125, 5, &client));
+
+        assert_eq!(ob.spread(), Some(0.875));
+        assert_eq!(ob.spread_range(), Some((0.125, 0.875)));
+    }
 }